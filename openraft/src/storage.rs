@@ -1,5 +1,7 @@
 //! The Raft storage interface and data types.
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use std::ops::RangeBounds;
 
@@ -10,7 +12,6 @@ use tokio::io::AsyncRead;
 use tokio::io::AsyncSeek;
 use tokio::io::AsyncWrite;
 
-use crate::core::EffectiveMembership;
 use crate::defensive::check_range_matches_entries;
 use crate::raft::Entry;
 use crate::raft::EntryPayload;
@@ -44,10 +45,131 @@ where S: AsyncRead + AsyncSeek + Send + Unpin + 'static
     pub snapshot: Box<S>,
 }
 
+/// The state about logs.
+///
+/// `last_purged_log_id` marks the prefix that has been purged from the log (e.g. after being
+/// absorbed into a snapshot). Indices `<= last_purged_log_id` are known to be intentionally gone,
+/// not missing due to corruption, which lets callers stop scanning once they reach this point
+/// instead of treating a purged hole as an error.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogState {
+    /// The last purged log id, inclusive.
+    pub last_purged_log_id: Option<LogId>,
+
+    /// The last known log id in the log store, which is the same as `last_purged_log_id` if the
+    /// log is empty after being fully purged.
+    pub last_log_id: Option<LogId>,
+}
+
+/// A trait defining the minimal address/data payload an application attaches to a node id in
+/// membership. Implementing this over a bare `u64` id lets Raft replicate the connection info
+/// needed to actually reach a peer as part of the membership config itself, instead of relying on
+/// an external, separately-maintained id-to-address map.
+///
+/// [`Membership`] is generic over `N: Node`, so an application can swap in its own payload (e.g.
+/// one carrying TLS info or a region tag) in place of [`BasicNode`].
+pub trait Node: Clone + Debug + Default + Eq + PartialEq + Serialize + for<'a> Deserialize<'a> + Send + Sync + 'static {}
+
+impl Node for BasicNode {}
+
+/// A basic, ready-to-use [`Node`] implementation: a network address plus a free-form data bag.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BasicNode {
+    /// The address other nodes should use to reach this node, e.g. `host:port`.
+    pub addr: String,
+
+    /// Arbitrary application-defined data associated with this node.
+    pub data: BTreeMap<String, String>,
+}
+
+impl BasicNode {
+    pub fn new(addr: impl ToString) -> Self {
+        Self {
+            addr: addr.to_string(),
+            data: BTreeMap::new(),
+        }
+    }
+}
+
+/// A membership config: the voter/learner id sets, plus the address/data record of every node
+/// they refer to.
+///
+/// Carrying the node records as a field of the membership config itself -- rather than alongside
+/// it -- makes connection info a first-class, consensus-replicated part of
+/// `EntryPayload::Membership`: a newly joined node learns every peer's address atomically with
+/// the config change, instead of out of band.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Membership<N: Node = BasicNode> {
+    /// Ids of the voting members.
+    pub voters: BTreeSet<NodeId>,
+
+    /// Ids of the non-voting members.
+    pub learners: BTreeSet<NodeId>,
+
+    /// The address/data record for every node referenced in `voters` or `learners`.
+    pub nodes: BTreeMap<NodeId, N>,
+}
+
+impl<N: Node> Default for Membership<N> {
+    fn default() -> Self {
+        Self {
+            voters: BTreeSet::new(),
+            learners: BTreeSet::new(),
+            nodes: BTreeMap::new(),
+        }
+    }
+}
+
+impl<N: Node> Membership<N> {
+    pub fn new(voters: BTreeSet<NodeId>, learners: BTreeSet<NodeId>, nodes: BTreeMap<NodeId, N>) -> Self {
+        Self {
+            voters,
+            learners,
+            nodes,
+        }
+    }
+
+    /// Returns the address/data record for every node in this membership.
+    pub fn nodes(&self) -> &BTreeMap<NodeId, N> {
+        &self.nodes
+    }
+}
+
+/// A membership config as persisted in either the log or the state machine, paired with the
+/// `LogId` it was read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredMembership<N: Node = BasicNode> {
+    /// The log id this membership was read from; `None` for the default membership a node starts
+    /// with before any membership change has gone through the log.
+    pub log_id: Option<LogId>,
+
+    /// The membership config, including every member's node record.
+    pub membership: Membership<N>,
+}
+
+impl<N: Node> Default for StoredMembership<N> {
+    fn default() -> Self {
+        Self {
+            log_id: None,
+            membership: Membership::default(),
+        }
+    }
+}
+
+impl<N: Node> StoredMembership<N> {
+    pub fn new(log_id: Option<LogId>, membership: Membership<N>) -> Self {
+        Self { log_id, membership }
+    }
+}
+
 /// A record holding the hard state of a Raft node.
 ///
 /// This model derives serde's traits for easily (de)serializing this
 /// model for storage & retrieval.
+///
+/// Deprecated: superseded by [`Vote`], which gives persisted votes a total order instead of
+/// requiring every reader to re-derive "is this newer than what I have" by hand. Kept around,
+/// with a [`From`]/[`Into`] bridge, so existing stores can migrate incrementally.
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Default)]
 pub struct HardState {
     /// The last recorded term observed by this system.
@@ -56,57 +178,179 @@ pub struct HardState {
     pub voted_for: Option<NodeId>,
 }
 
+impl From<HardState> for Vote {
+    fn from(hs: HardState) -> Self {
+        // `HardState` has no notion of a committed vote, so the migrated value is always
+        // uncommitted; the engine re-derives `committed` once it resumes and re-establishes
+        // leadership rather than trusting a pre-migration store.
+        Vote {
+            term: hs.current_term,
+            node_id: hs.voted_for,
+            committed: false,
+        }
+    }
+}
+
+impl From<Vote> for HardState {
+    fn from(v: Vote) -> Self {
+        HardState {
+            current_term: v.term,
+            voted_for: v.node_id,
+        }
+    }
+}
+
+/// A persisted vote, comparable as a single totally-ordered value.
+///
+/// Votes are compared by `term` first; for an equal `term`, a `committed` vote -- one an elected
+/// leader has confirmed with a quorum -- is greater than an uncommitted one, and only then are
+/// ties broken by `node_id`. Ordering on `node_id` alone within the same term would let any node
+/// with a numerically larger id override an already-established leader's vote, a double-vote /
+/// leader-override bug; `committed` is what makes an established leader's vote un-overridable by
+/// a mere candidate in the same term. This lets the engine express "is this vote newer than what
+/// I have persisted?" as a plain `incoming > stored` comparison instead of re-implementing the
+/// rule at every read site.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub struct Vote {
+    /// The term of this vote.
+    pub term: u64,
+    /// The node this vote was granted to in `term`, if any.
+    pub node_id: Option<NodeId>,
+    /// Whether this vote has been confirmed by a quorum, i.e. `node_id` is an established leader
+    /// for `term` rather than merely a candidate that has requested votes.
+    pub committed: bool,
+}
+
+impl Vote {
+    pub fn new(term: u64, node_id: Option<NodeId>) -> Self {
+        Self {
+            term,
+            node_id,
+            committed: false,
+        }
+    }
+
+    pub fn new_committed(term: u64, node_id: NodeId) -> Self {
+        Self {
+            term,
+            node_id: Some(node_id),
+            committed: true,
+        }
+    }
+
+    /// Mark this vote as committed, once its node has confirmed leadership with a quorum.
+    pub fn commit(&mut self) {
+        self.committed = true;
+    }
+}
+
+impl PartialOrd for Vote {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Vote {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.term
+            .cmp(&other.term)
+            .then_with(|| self.committed.cmp(&other.committed))
+            .then_with(|| self.node_id.cmp(&other.node_id))
+    }
+}
+
 /// A struct used to represent the initial state which a Raft node needs when first starting.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
-pub struct InitialState {
+pub struct InitialState<N: Node = BasicNode> {
     /// The last entry.
     pub last_log_id: Option<LogId>,
 
     /// The LogId of the last log applied to the state machine.
     pub last_applied: Option<LogId>,
 
-    /// The saved hard state of the node.
-    pub hard_state: HardState,
+    /// The saved vote of the node.
+    pub vote: Vote,
 
     /// The latest cluster membership configuration found, in log or in state machine, else a new initial
     /// membership config consisting only of this node's ID.
-    pub last_membership: Option<EffectiveMembership>,
+    pub last_membership: Option<StoredMembership<N>>,
 }
 
-/// A trait defining the interface for a Raft storage system.
+/// A trait defining the interface for a Raft log reader.
+///
+/// Log reader is a subset of the [`RaftStorage`] trait that is used for reading log entries. This
+/// trait is separated from [`RaftStorage`] so that a reader handle can be cheaply cloned and
+/// handed to replication streams or snapshot builders, letting them read concurrently with the
+/// main storage task that appends to the log.
+///
+/// An implementation may use the same underlying database for both [`RaftLogReader`] and
+/// [`RaftStorage`], e.g. a read-only transaction/view that is unaffected by concurrent writes.
 ///
 /// See the [storage chapter of the guide](https://datafuselabs.github.io/openraft/storage.html)
 /// for details and discussion on this trait and how to implement it.
 #[async_trait]
-pub trait RaftStorage<D, R>: Send + Sync + 'static
+pub trait RaftLogReader<D, N = BasicNode>: Send + Sync + 'static
 where
     D: AppData,
-    R: AppDataResponse,
+    N: Node,
 {
-    // TODO(xp): simplify storage API
+    /// Get a series of log entries from storage.
+    ///
+    /// The start value is inclusive in the search and the stop value is non-inclusive: `[start, stop)`.
+    ///
+    /// Entry that is not found is allowed.
+    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + Send + Sync>(
+        &mut self,
+        range: RB,
+    ) -> Result<Vec<Entry<D>>, StorageError>;
 
-    /// The storage engine's associated type used for exposing a snapshot for reading & writing.
+    /// Get a series of log entries from storage.
     ///
-    /// See the [storage chapter of the guide](https://datafuselabs.github.io/openraft/getting-started.html#implement-raftstorage)
-    /// for details on where and how this is used.
-    type SnapshotData: AsyncRead + AsyncWrite + AsyncSeek + Send + Sync + Unpin + 'static;
+    /// Similar to `try_get_log_entries` except an error will be returned if there is an entry not found in the
+    /// specified range.
+    async fn get_log_entries<RB: RangeBounds<u64> + Clone + Debug + Send + Sync>(
+        &mut self,
+        range: RB,
+    ) -> Result<Vec<Entry<D>>, StorageError> {
+        // TODO(xp): test: expect an error if a specified entry is not found
+        let res = self.try_get_log_entries(range.clone()).await?;
 
-    /// Returns the last membership config found in log or state machine.
-    async fn get_membership(&self) -> Result<Option<EffectiveMembership>, StorageError> {
-        let (_, sm_mem) = self.last_applied_state().await?;
+        check_range_matches_entries(range, &res)?;
 
-        let sm_mem_index = match &sm_mem {
-            None => 0,
-            Some(mem) => mem.log_id.index,
-        };
+        Ok(res)
+    }
 
-        let log_mem = self.last_membership_in_log(sm_mem_index + 1).await?;
+    /// Try to get an log entry.
+    ///
+    /// It does not return an error if the log entry at `log_index` is not found.
+    async fn try_get_log_entry(&mut self, log_index: u64) -> Result<Option<Entry<D>>, StorageError> {
+        let mut res = self.try_get_log_entries(log_index..(log_index + 1)).await?;
+        Ok(res.pop())
+    }
 
-        if log_mem.is_some() {
-            return Ok(log_mem);
-        }
+    /// Returns the state of the log, including the last purged log id and the last log id.
+    ///
+    /// The impl should not consider the applied log id in state machine.
+    async fn get_log_state(&mut self) -> Result<LogState, StorageError>;
 
-        return Ok(sm_mem);
+    /// Returns the first log id in log.
+    ///
+    /// The log is taken to start at its last purged log id, so that is the id returned (`None`
+    /// if nothing has been purged); it is no longer present in the log itself. Indices at or
+    /// before it are known to be intentionally gone, not missing due to corruption.
+    ///
+    /// The impl should not consider the applied log id in state machine.
+    async fn first_id_in_log(&mut self) -> Result<Option<LogId>, StorageError> {
+        let log_state = self.get_log_state().await?;
+        Ok(log_state.last_purged_log_id)
+    }
+
+    /// Returns the last log id in log.
+    ///
+    /// The impl should not consider the applied log id in state machine.
+    async fn last_id_in_log(&mut self) -> Result<Option<LogId>, StorageError> {
+        let log_state = self.get_log_state().await?;
+        Ok(log_state.last_log_id)
     }
 
     /// Get the latest membership config found in the log.
@@ -114,10 +358,10 @@ where
     /// This method should returns membership with the greatest log index which is `>=since_index`.
     /// If no such membership log is found, it returns `None`, e.g., when logs are cleaned after being applied.
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn last_membership_in_log(&self, since_index: u64) -> Result<Option<EffectiveMembership>, StorageError> {
-        let (first_log_id, last_log_id) = self.get_log_state().await?;
+    async fn last_membership_in_log(&mut self, since_index: u64) -> Result<Option<StoredMembership<N>>, StorageError> {
+        let log_state = self.get_log_state().await?;
 
-        let first_log_id = match first_log_id {
+        let last_log_id = match log_state.last_log_id {
             None => {
                 // There is no log at all
                 return Ok(None);
@@ -125,19 +369,21 @@ where
             Some(x) => x,
         };
 
-        let mut end = last_log_id.unwrap().index + 1;
-        let start = std::cmp::max(first_log_id.index, since_index);
+        let mut end = last_log_id.index + 1;
+        // Indices <= last_purged_log_id are known to be purged, not corruption; stop the scan
+        // there instead of walking into the hole.
+        let purged_end = log_state.last_purged_log_id.map(|x| x.index + 1).unwrap_or(0);
+        let start = std::cmp::max(purged_end, since_index);
         let step = 64;
 
         while start < end {
             let entries = self.try_get_log_entries(start..end).await?;
 
             for ent in entries.iter().rev() {
+                // `EntryPayload::Membership` carries a `Membership<N>`, so the node records for
+                // every voter/learner travel with the config change itself.
                 if let EntryPayload::Membership(ref mem) = ent.payload {
-                    return Ok(Some(EffectiveMembership {
-                        log_id: ent.log_id,
-                        membership: mem.clone(),
-                    }));
+                    return Ok(Some(StoredMembership::new(Some(ent.log_id), mem.clone())));
                 }
             }
 
@@ -146,21 +392,101 @@ where
 
         Ok(None)
     }
+}
+
+/// A trait defining the interface for a Raft snapshot builder.
+///
+/// This is a subset of the [`RaftStorage`] trait that produces a [`Snapshot`] from a
+/// consistent point-in-time handle, so that building a snapshot -- often a long-running,
+/// CPU/IO-heavy compaction -- does not share a lock with `append_to_log` or
+/// `apply_to_state_machine` on the main storage task.
+///
+/// An implementation may use the same underlying database for both [`RaftSnapshotBuilder`] and
+/// [`RaftStorage`], e.g. a read-only transaction/view that is unaffected by concurrent writes.
+#[async_trait]
+pub trait RaftSnapshotBuilder<D, SD>: Send + Sync + 'static
+where
+    D: AppData,
+    SD: AsyncRead + AsyncWrite + AsyncSeek + Send + Sync + Unpin + 'static,
+{
+    /// Build snapshot
+    ///
+    /// A snapshot has to contain information about exactly all logs upto the last applied.
+    ///
+    /// Building snapshot can be done by:
+    /// - Performing log compaction, e.g. merge log entries that operates on the same key, like a LSM-tree does,
+    /// - or by fetching a snapshot from the state machine.
+    async fn build_snapshot(&mut self) -> Result<Snapshot<SD>, StorageError>;
+}
+
+/// A trait defining the interface for a Raft storage system.
+///
+/// See the [storage chapter of the guide](https://datafuselabs.github.io/openraft/storage.html)
+/// for details and discussion on this trait and how to implement it.
+#[async_trait]
+pub trait RaftStorage<D, R, N = BasicNode>: Send + Sync + 'static
+where
+    D: AppData,
+    R: AppDataResponse,
+    N: Node,
+{
+    // TODO(xp): simplify storage API
+
+    /// The storage engine's associated type used for exposing a snapshot for reading & writing.
+    ///
+    /// See the [storage chapter of the guide](https://datafuselabs.github.io/openraft/getting-started.html#implement-raftstorage)
+    /// for details on where and how this is used.
+    type SnapshotData: AsyncRead + AsyncWrite + AsyncSeek + Send + Sync + Unpin + 'static;
+
+    /// The concrete read-only log reader type used by this store, returned by [`Self::get_log_reader`].
+    ///
+    /// A reader can be cheaply cloned/constructed so replication and snapshotting can read logs
+    /// concurrently with the main storage task.
+    type LogReader: RaftLogReader<D, N>;
+
+    /// The concrete snapshot builder type used by this store, returned by [`Self::get_snapshot_builder`].
+    ///
+    /// A builder can be constructed from a consistent point-in-time handle so that snapshotting
+    /// can run off the main storage task, concurrently with log appends and state machine applies.
+    type SnapshotBuilder: RaftSnapshotBuilder<D, Self::SnapshotData>;
+
+    /// Returns the last membership config found in log or state machine.
+    async fn get_membership(&self) -> Result<Option<StoredMembership<N>>, StorageError> {
+        let (_, sm_mem) = self.last_applied_state().await?;
+
+        let sm_mem_index = match &sm_mem {
+            None => 0,
+            Some(mem) => mem.log_id.map(|x| x.index).unwrap_or(0),
+        };
+
+        let mut log_reader = self.get_log_reader().await;
+        let log_mem = log_reader.last_membership_in_log(sm_mem_index + 1).await?;
+
+        if log_mem.is_some() {
+            return Ok(log_mem);
+        }
+
+        return Ok(sm_mem);
+    }
 
     /// Returns the first log id in log.
     ///
+    /// The log is taken to start at its last purged log id, so that is the id returned (`None`
+    /// if nothing has been purged); it is no longer present in the log itself. Indices at or
+    /// before it are known to be intentionally gone, not missing due to corruption.
+    ///
     /// The impl should not consider the applied log id in state machine.
     async fn first_id_in_log(&self) -> Result<Option<LogId>, StorageError> {
-        let (first_log_id, _) = self.get_log_state().await?;
-        Ok(first_log_id)
+        let mut log_reader = self.get_log_reader().await;
+        log_reader.first_id_in_log().await
     }
 
     /// Returns the last log id in log.
     ///
     /// The impl should not consider the applied log id in state machine.
     async fn last_id_in_log(&self) -> Result<Option<LogId>, StorageError> {
-        let (_, last_log_id) = self.get_log_state().await?;
-        Ok(last_log_id)
+        let mut log_reader = self.get_log_reader().await;
+        log_reader.last_id_in_log().await
     }
 
     /// Returns first known log id in logs or in state machine.
@@ -168,7 +494,9 @@ where
     /// It returns None only when there is never a log.
     async fn first_known_log_id(&self) -> Result<Option<LogId>, StorageError> {
         let (last_applied, _) = self.last_applied_state().await?;
-        let (first, _) = self.get_log_state().await?;
+        let mut log_reader = self.get_log_reader().await;
+        let log_state = log_reader.get_log_state().await?;
+        let first = log_state.last_purged_log_id;
 
         if last_applied.is_none() {
             return Ok(first);
@@ -185,8 +513,8 @@ where
     ///
     /// When the Raft node is first started, it will call this interface to fetch the last known state from stable
     /// storage.
-    async fn get_initial_state(&self) -> Result<InitialState, StorageError> {
-        let hs = self.read_hard_state().await?;
+    async fn get_initial_state(&self) -> Result<InitialState<N>, StorageError> {
+        let vote = self.read_vote().await?;
 
         // Search for two place and use the max one,
         // because when a state machine is installed there could be logs
@@ -203,57 +531,33 @@ where
         Ok(InitialState {
             last_log_id,
             last_applied,
-            hard_state: hs.unwrap_or_default(),
+            vote: vote.unwrap_or_default(),
             last_membership: membership,
         })
     }
 
-    /// Get a series of log entries from storage.
-    ///
-    /// Similar to `try_get_log_entries` except an error will be returned if there is an entry not found in the
-    /// specified range.
-    async fn get_log_entries<RB: RangeBounds<u64> + Clone + Debug + Send + Sync>(
-        &self,
-        range: RB,
-    ) -> Result<Vec<Entry<D>>, StorageError> {
-        // TODO(xp): test: expect an error if a specified entry is not found
-        let res = self.try_get_log_entries(range.clone()).await?;
-
-        check_range_matches_entries(range, &res)?;
+    // --- Vote
 
-        Ok(res)
-    }
-
-    /// Try to get an log entry.
+    /// Save the given vote to stable storage.
     ///
-    /// It does not return an error if the log entry at `log_index` is not found.
-    async fn try_get_log_entry(&self, log_index: u64) -> Result<Option<Entry<D>>, StorageError> {
-        let mut res = self.try_get_log_entries(log_index..(log_index + 1)).await?;
-        Ok(res.pop())
-    }
-
-    // --- Hard State
+    /// Rejecting a vote that is not `>=` the currently persisted one is the caller's
+    /// responsibility; this method unconditionally overwrites the persisted vote.
+    async fn save_vote(&self, vote: &Vote) -> Result<(), StorageError>;
 
-    async fn save_hard_state(&self, hs: &HardState) -> Result<(), StorageError>;
-
-    async fn read_hard_state(&self) -> Result<Option<HardState>, StorageError>;
+    /// Read the vote persisted by the most recent call to `save_vote`.
+    async fn read_vote(&self) -> Result<Option<Vote>, StorageError>;
 
     // --- Log
 
-    /// Returns the fist log id and last log id in log.
+    /// Get a handle to a log reader.
     ///
-    /// The impl should not consider the applied log id in state machine.
-    async fn get_log_state(&self) -> Result<(Option<LogId>, Option<LogId>), StorageError>;
-
-    /// Get a series of log entries from storage.
-    ///
-    /// The start value is inclusive in the search and the stop value is non-inclusive: `[start, stop)`.
-    ///
-    /// Entry that is not found is allowed.
-    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + Send + Sync>(
-        &self,
-        range: RB,
-    ) -> Result<Vec<Entry<D>>, StorageError>;
+    /// The returned instance should be able to run concurrently with the main storage task, so
+    /// that replication and snapshotting can read the log without contending with `append_to_log`.
+    /// The last purged log id, the last log id, and every other read derived from them (see
+    /// [`RaftLogReader::get_log_state`]) are obtained through this reader rather than a second,
+    /// separately-required method on this trait, so there is exactly one source of truth for log
+    /// state.
+    async fn get_log_reader(&self) -> Self::LogReader;
 
     /// Append a payload of entries to the log.
     ///
@@ -261,19 +565,29 @@ where
     /// determine its location to be written in the log.
     async fn append_to_log(&self, entries: &[&Entry<D>]) -> Result<(), StorageError>;
 
-    /// Delete all logs in a `range`.
+    /// Purge the log up to and including `log_id`, recording it as the new `last_purged_log_id`.
+    ///
+    /// This is called after a snapshot has absorbed the committed/applied prefix of the log.
+    /// Unlike [`Self::delete_conflict_logs_since`], the deleted range is known-good and durably
+    /// compacted, so the store must remember the watermark (via `RaftLogReader::get_log_state`)
+    /// rather than just dropping the rows.
     ///
     /// Errors returned from this method will cause Raft to go into shutdown.
-    async fn delete_log<RB: RangeBounds<u64> + Clone + Debug + Send + Sync>(
-        &self,
-        range: RB,
-    ) -> Result<(), StorageError>;
+    async fn purge_logs_upto(&self, log_id: LogId) -> Result<(), StorageError>;
+
+    /// Delete conflicting log entries since `log_id`, inclusive, to the end of the log.
+    ///
+    /// This is called after an `AppendEntries` mismatch is detected, to truncate the tail of
+    /// entries that conflict with the leader's log before the matching entries are appended.
+    ///
+    /// Errors returned from this method will cause Raft to go into shutdown.
+    async fn delete_conflict_logs_since(&self, log_id: LogId) -> Result<(), StorageError>;
 
     // --- State Machine
 
     /// Returns the last applied log id which is recorded in state machine, and the last applied membership log id and
     /// membership config.
-    async fn last_applied_state(&self) -> Result<(Option<LogId>, Option<EffectiveMembership>), StorageError>;
+    async fn last_applied_state(&self) -> Result<(Option<LogId>, Option<StoredMembership<N>>), StorageError>;
 
     /// Apply the given payload of entries to the state machine.
     ///
@@ -293,14 +607,11 @@ where
 
     // --- Snapshot
 
-    /// Build snapshot
-    ///
-    /// A snapshot has to contain information about exactly all logs upto the last applied.
+    /// Get a handle to a snapshot builder.
     ///
-    /// Building snapshot can be done by:
-    /// - Performing log compaction, e.g. merge log entries that operates on the same key, like a LSM-tree does,
-    /// - or by fetching a snapshot from the state machine.
-    async fn build_snapshot(&self) -> Result<Snapshot<Self::SnapshotData>, StorageError>;
+    /// The returned instance is built from a consistent point-in-time handle, so that compaction
+    /// can run concurrently with the main storage task instead of sharing its lock.
+    async fn get_snapshot_builder(&self) -> Self::SnapshotBuilder;
 
     /// Create a new blank snapshot, returning a writable handle to the snapshot object.
     ///
@@ -343,3 +654,244 @@ pub trait RaftStorageDebug<SM> {
     /// Get a handle to the state machine for testing purposes.
     async fn get_state_machine(&self) -> SM;
 }
+
+/// The default cap on the number of entries a [`LogCache`] retains; see [`LogCache::with_capacity`]
+/// to override it.
+pub const DEFAULT_LOG_CACHE_CAPACITY: usize = 1024;
+
+/// An in-memory, write-through cache of recently appended/replicated log entries, bounded to at
+/// most `capacity` entries.
+///
+/// This sits between Raft core and a [`RaftStorage`] implementation so that `apply_to_state_machine`
+/// can serve entries from memory instead of round-tripping to disk for every commit. It is
+/// populated in the same step that writes to the log (so a committed index is never absent from
+/// both), and entries are evicted once their index is `<= last_applied` or after a snapshot is
+/// installed, since at that point the state machine -- not the cache -- is the source of truth.
+///
+/// A follower that replicates faster than it applies would otherwise grow this cache without
+/// bound, so `insert` also evicts the newest (highest-index) entries once `capacity` is exceeded --
+/// those are the ones furthest from being applied, so there is the most time to refetch them from
+/// storage before `apply_to_state_machine` actually needs them. Evicting the lowest-index entries
+/// instead would guarantee a cache miss on the very next apply, defeating the cache.
+///
+/// Truncation on conflict is critical: whenever [`RaftStorage::delete_conflict_logs_since`] removes
+/// a tail of the log, the matching tail must be dropped from this cache in the same step, or a
+/// stale overwritten entry could be served and applied.
+#[derive(Debug)]
+pub struct LogCache<D>
+where D: AppData
+{
+    cache: std::collections::BTreeMap<u64, Entry<D>>,
+    capacity: usize,
+}
+
+impl<D> Default for LogCache<D>
+where D: AppData
+{
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_LOG_CACHE_CAPACITY)
+    }
+}
+
+impl<D> LogCache<D>
+where D: AppData
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a cache that retains at most `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            cache: std::collections::BTreeMap::new(),
+            capacity,
+        }
+    }
+
+    /// Insert entries appended/replicated to the log.
+    ///
+    /// Must be called in the same step as the corresponding `append_to_log`, so there is never a
+    /// window where a committed index is absent from both the log and the cache. If this pushes
+    /// the cache over `capacity`, the newest (highest-index) entries are evicted first, so an
+    /// appended entry may be present only in the log.
+    pub fn insert(&mut self, entries: &[&Entry<D>]) {
+        for ent in entries {
+            self.cache.insert(ent.log_id.index, (*ent).clone());
+        }
+        self.enforce_capacity();
+    }
+
+    fn enforce_capacity(&mut self) {
+        while self.cache.len() > self.capacity {
+            let newest = match self.cache.keys().next_back().copied() {
+                Some(k) => k,
+                None => break,
+            };
+            self.cache.remove(&newest);
+        }
+    }
+
+    /// Drop every cached entry at or after `log_id`, mirroring `delete_conflict_logs_since`.
+    ///
+    /// Must be called in the same step as the corresponding storage call, so a stale,
+    /// since-overwritten entry is never served from the cache.
+    pub fn truncate_since(&mut self, log_id: LogId) {
+        self.cache.split_off(&log_id.index);
+    }
+
+    /// Drop every cached entry at or before `last_applied`, e.g. the last log id of a newly
+    /// installed snapshot. Entries after it are kept.
+    pub fn evict_through(&mut self, last_applied: LogId) {
+        let tail = self.cache.split_off(&(last_applied.index + 1));
+        self.cache = tail;
+    }
+
+    /// Serve `[start, stop)` from the cache, returning `None` on any miss in the range so the
+    /// caller can fall back to `try_get_log_entries`.
+    pub fn get(&self, start: u64, stop: u64) -> Option<Vec<Entry<D>>> {
+        if stop <= start {
+            return Some(Vec::new());
+        }
+
+        let mut out = Vec::with_capacity((stop - start) as usize);
+
+        for index in start..stop {
+            match self.cache.get(&index) {
+                Some(ent) => out.push(ent.clone()),
+                None => return None,
+            }
+        }
+
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(term: u64, index: u64) -> Entry<()> {
+        Entry {
+            log_id: LogId { term, index },
+            payload: EntryPayload::Blank,
+        }
+    }
+
+    #[test]
+    fn log_cache_insert_and_get_round_trip() {
+        let mut cache = LogCache::new();
+        let entries = vec![entry(1, 1), entry(1, 2), entry(1, 3)];
+        let refs: Vec<&Entry<()>> = entries.iter().collect();
+
+        cache.insert(&refs);
+
+        let got = cache.get(1, 4).expect("all of 1..4 were inserted");
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[0].log_id.index, 1);
+        assert_eq!(got[2].log_id.index, 3);
+    }
+
+    #[test]
+    fn log_cache_get_misses_on_a_gap() {
+        let mut cache = LogCache::new();
+        let e1 = entry(1, 1);
+        let e3 = entry(1, 3);
+
+        cache.insert(&[&e1, &e3]);
+
+        assert_eq!(cache.get(1, 4), None, "index 2 is missing, so the range cannot be served from cache");
+    }
+
+    #[test]
+    fn log_cache_get_with_empty_range_returns_empty_vec() {
+        let cache: LogCache<()> = LogCache::new();
+
+        assert_eq!(cache.get(5, 5), Some(Vec::new()));
+        assert_eq!(cache.get(5, 1), Some(Vec::new()));
+    }
+
+    #[test]
+    fn log_cache_truncate_since_drops_the_conflicting_tail() {
+        let mut cache = LogCache::new();
+        let entries = vec![entry(1, 1), entry(1, 2), entry(1, 3)];
+        let refs: Vec<&Entry<()>> = entries.iter().collect();
+        cache.insert(&refs);
+
+        cache.truncate_since(LogId { term: 1, index: 2 });
+
+        assert!(cache.get(1, 2).is_some());
+        assert_eq!(cache.get(1, 3), None);
+    }
+
+    #[test]
+    fn log_cache_evict_through_drops_applied_prefix() {
+        let mut cache = LogCache::new();
+        let entries = vec![entry(1, 1), entry(1, 2), entry(1, 3)];
+        let refs: Vec<&Entry<()>> = entries.iter().collect();
+        cache.insert(&refs);
+
+        cache.evict_through(LogId { term: 1, index: 2 });
+
+        assert_eq!(cache.get(1, 3), None, "index 1 was evicted");
+        let remaining = cache.get(3, 4).expect("index 3 is still past the applied watermark");
+        assert_eq!(remaining[0].log_id.index, 3);
+    }
+
+    #[test]
+    fn log_cache_insert_evicts_newest_entries_past_capacity() {
+        let mut cache = LogCache::with_capacity(2);
+        let entries = vec![entry(1, 1), entry(1, 2), entry(1, 3)];
+        let refs: Vec<&Entry<()>> = entries.iter().collect();
+
+        cache.insert(&refs);
+
+        assert_eq!(cache.get(3, 4), None, "the newest entry must be evicted once capacity is exceeded");
+        let kept = cache.get(1, 3).expect("the two oldest, soonest-to-be-applied entries must be retained");
+        assert_eq!(kept.len(), 2);
+    }
+
+    #[test]
+    fn vote_orders_by_term_first() {
+        let lower_term = Vote::new(1, Some(5));
+        let higher_term = Vote::new(2, Some(1));
+
+        assert!(higher_term > lower_term);
+    }
+
+    #[test]
+    fn vote_committed_beats_uncommitted_in_same_term() {
+        let candidate = Vote::new(1, Some(2));
+        let leader = Vote::new_committed(1, 1);
+
+        assert!(leader > candidate, "a committed leader vote must outrank any uncommitted vote in the same term");
+    }
+
+    #[test]
+    fn vote_same_term_uncommitted_breaks_tie_on_node_id() {
+        let lower_node = Vote::new(1, Some(1));
+        let higher_node = Vote::new(1, Some(2));
+
+        assert!(higher_node > lower_node);
+    }
+
+    #[test]
+    fn committed_leader_vote_cannot_be_overridden_by_higher_node_id() {
+        // Regression test: term/node_id ordering alone would let a higher node id override an
+        // already-established leader's vote within the same term. `committed` must prevent this.
+        let stored = Vote::new_committed(1, 1);
+        let incoming = Vote::new(1, Some(2));
+
+        assert!(!(incoming > stored), "a mere candidate must not be able to override a committed leader's vote");
+    }
+
+    #[test]
+    fn vote_commit_raises_ordering_without_changing_identity() {
+        let mut v = Vote::new(1, Some(1));
+        let before = v;
+        v.commit();
+
+        assert_eq!(v.term, before.term);
+        assert_eq!(v.node_id, before.node_id);
+        assert!(v > before);
+    }
+}